@@ -0,0 +1,693 @@
+// Copyright 2018-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Macro for declaring a complete test-client harness for a given runtime.
+
+/// Declares the full set of test-client types and constructors for `$runtime`, so that a
+/// downstream chain can stand up a typed `TestClient`/`LightBackend`/`LightFetcher` against its
+/// own `RuntimeApi` without copy-pasting this module.
+///
+/// Emits `Backend`, `Executor`, `LightBackend`, `LightExecutor`, `GenesisParameters`,
+/// `TestClientBuilder`, `Client<B>`, `TestClient`, the `DefaultTestClientBuilderExt` and
+/// `TestClientBuilderExt` impls, the `LocalExecutor` via `native_executor_instance!`, a
+/// verifying `LightFetcher`, and the `new()` / `new_light()` / `new_light_fetcher()`
+/// constructors — exactly the items this crate declares for `runtime` in `lib.rs` by invoking
+/// this macro.
+///
+/// `$runtime` is the runtime crate's name (a single identifier) and must expose `Block` and
+/// `RuntimeApi` types. `$genesis_config_ctor` must have the same parameter list as
+/// `runtime::genesismap::GenesisConfig::new` and return a value with a `genesis_map` method,
+/// and `$additional_storage_with_genesis` must have the same signature as
+/// `runtime::genesismap::additional_storage_with_genesis`.
+///
+/// All other dependencies the generated code needs (`client`, `client_api`, `client_db`,
+/// `executor`, `codec`, `state_machine`, `futures`, `primitives`, `sp_blockchain`,
+/// `sp_runtime`, `generic_test_client`) are reached through `$crate::`, which this crate
+/// re-exports them under for
+/// exactly this purpose — the invoking crate does not need to depend on any of them itself,
+/// only on this crate and its own `$runtime`.
+#[macro_export]
+macro_rules! decl_test_client {
+	(
+		runtime: $runtime:ident,
+		dispatch: $dispatch:path,
+		native_version: $native_version:path,
+		authority_id: $authority_id:ty,
+		account_id: $account_id:ty,
+		genesis_config_ctor: $genesis_config_ctor:path,
+		additional_storage_with_genesis: $additional_storage_with_genesis:path,
+	) => {
+		mod local_executor {
+			#![allow(missing_docs)]
+			use $crate::executor::native_executor_instance;
+			// FIXME #1576 change the macro and pass in the `BlakeHasher` that dispatch needs from here instead
+			native_executor_instance!(
+				pub LocalExecutor,
+				$dispatch,
+				$native_version
+			);
+		}
+
+		/// Native executor used for tests.
+		pub use local_executor::LocalExecutor;
+
+		/// Test client database backend.
+		pub type Backend = $crate::generic_test_client::Backend<$runtime::Block>;
+
+		/// Test client executor.
+		pub type Executor = $crate::client::LocalCallExecutor<
+			Backend,
+			$crate::NativeExecutor<LocalExecutor>,
+		>;
+
+		/// Test client light database backend.
+		pub type LightBackend = $crate::generic_test_client::LightBackend<$runtime::Block>;
+
+		/// Test client light executor.
+		pub type LightExecutor = $crate::client::light::call_executor::GenesisCallExecutor<
+			LightBackend,
+			$crate::client::LocalCallExecutor<
+				$crate::client::light::backend::Backend<
+					$crate::client_db::light::LightStorage<$runtime::Block>,
+					$crate::sp_runtime::traits::HasherFor<$runtime::Block>
+				>,
+				$crate::NativeExecutor<LocalExecutor>
+			>
+		>;
+
+		/// Parameters of test-client builder with this runtime.
+		#[derive(Default, Clone)]
+		pub struct GenesisParameters {
+			support_changes_trie: bool,
+			heap_pages_override: Option<u64>,
+			extra_storage: std::collections::HashMap<Vec<u8>, Vec<u8>>,
+			child_extra_storage: std::collections::HashMap<Vec<u8>, std::collections::HashMap<Vec<u8>, Vec<u8>>>,
+			authorities: Option<Vec<$authority_id>>,
+			endowed_accounts: Option<Vec<$account_id>>,
+			initial_balance: Option<u128>,
+		}
+
+		impl $crate::generic_test_client::GenesisInit for GenesisParameters {
+			fn genesis_storage(&self) -> ($crate::StorageOverlay, $crate::ChildrenStorageOverlay) {
+				use $crate::codec::Encode;
+				use $crate::sp_runtime::traits::{Block as BlockT, Header as HeaderT, Hash as HashT};
+
+				let authorities = self.authorities.clone().unwrap_or_else(|| vec![
+					$crate::primitives::sr25519::Public::from($crate::Sr25519Keyring::Alice).into(),
+					$crate::primitives::sr25519::Public::from($crate::Sr25519Keyring::Bob).into(),
+					$crate::primitives::sr25519::Public::from($crate::Sr25519Keyring::Charlie).into(),
+				]);
+				let endowed_accounts = self.endowed_accounts.clone().unwrap_or_else(|| vec![
+					$crate::AccountKeyring::Alice.into(),
+					$crate::AccountKeyring::Bob.into(),
+					$crate::AccountKeyring::Charlie.into(),
+				]);
+				let initial_balance = self.initial_balance.unwrap_or(1000);
+
+				let mut storage = $genesis_config_ctor(
+					self.support_changes_trie,
+					authorities,
+					endowed_accounts,
+					initial_balance,
+					self.heap_pages_override,
+					self.extra_storage.clone(),
+					self.child_extra_storage.clone(),
+				).genesis_map();
+
+				let child_roots = storage.1.iter().map(|(sk, child_map)| {
+					let state_root = <<<$runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+						child_map.clone().into_iter().collect()
+					);
+					(sk.clone(), state_root.encode())
+				});
+				let state_root = <<<$runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+					storage.0.clone().into_iter().chain(child_roots).collect()
+				);
+				let block: $runtime::Block = $crate::client::genesis::construct_genesis_block(state_root);
+				storage.0.extend($additional_storage_with_genesis(&block));
+
+				storage
+			}
+		}
+
+		/// A `TestClient` with this runtime's builder.
+		pub type TestClientBuilder<E, B> = $crate::generic_test_client::TestClientBuilder<E, B, GenesisParameters>;
+
+		/// Test client type with `LocalExecutor` and generic Backend.
+		pub type Client<B> = $crate::client::Client<
+			B,
+			$crate::client::LocalCallExecutor<B, $crate::executor::NativeExecutor<LocalExecutor>>,
+			$runtime::Block,
+			$runtime::RuntimeApi,
+		>;
+
+		/// A test client with default backend.
+		pub type TestClient = Client<Backend>;
+
+		/// A `TestClientBuilder` with default backend and executor.
+		pub trait DefaultTestClientBuilderExt: Sized {
+			/// Create new `TestClientBuilder`
+			fn new() -> Self;
+		}
+
+		impl DefaultTestClientBuilderExt for TestClientBuilder<Executor, Backend> {
+			fn new() -> Self {
+				Self::with_default_backend()
+			}
+		}
+
+		/// This runtime's extensions to `TestClientBuilder`.
+		pub trait TestClientBuilderExt<B>: Sized {
+			/// Returns a reference to the genesis parameters this builder will use, so that
+			/// other test helpers (e.g. a `LightFetcher`) built alongside this client can be
+			/// configured to verify against the same genesis rather than the default one.
+			fn genesis_init(&self) -> &GenesisParameters;
+
+			/// Returns a mutable reference to the genesis parameters.
+			fn genesis_init_mut(&mut self) -> &mut GenesisParameters;
+
+			/// Enable or disable support for changes trie in genesis.
+			fn set_support_changes_trie(mut self, support_changes_trie: bool) -> Self {
+				self.genesis_init_mut().support_changes_trie = support_changes_trie;
+				self
+			}
+
+			/// Override the default value for Wasm heap pages.
+			fn set_heap_pages(mut self, heap_pages: u64) -> Self {
+				self.genesis_init_mut().heap_pages_override = Some(heap_pages);
+				self
+			}
+
+			/// Override the default (Alice/Bob/Charlie) set of genesis authorities.
+			fn set_authorities(mut self, authorities: Vec<$authority_id>) -> Self {
+				self.genesis_init_mut().authorities = Some(authorities);
+				self
+			}
+
+			/// Override the default (Alice/Bob/Charlie) set of genesis endowed accounts.
+			fn set_endowed_accounts(mut self, endowed_accounts: Vec<$account_id>) -> Self {
+				self.genesis_init_mut().endowed_accounts = Some(endowed_accounts);
+				self
+			}
+
+			/// Override the default (1000) balance endowed accounts start out with.
+			fn set_initial_balance(mut self, initial_balance: u128) -> Self {
+				self.genesis_init_mut().initial_balance = Some(initial_balance);
+				self
+			}
+
+			/// Add an extra child value into the genesis storage.
+			///
+			/// # Panics
+			///
+			/// Panics if the key is empty.
+			fn add_extra_child_storage<SK: Into<Vec<u8>>, K: Into<Vec<u8>>, V: Into<Vec<u8>>>(
+				mut self,
+				storage_key: SK,
+				key: K,
+				value: V,
+			) -> Self {
+				let storage_key = storage_key.into();
+				let key = key.into();
+				assert!(!storage_key.is_empty());
+				assert!(!key.is_empty());
+				self.genesis_init_mut().child_extra_storage
+					.entry(storage_key)
+					.or_insert_with(Default::default)
+					.insert(key, value.into());
+				self
+			}
+
+			/// Add an extra value into the genesis storage.
+			///
+			/// # Panics
+			///
+			/// Panics if the key is empty.
+			fn add_extra_storage<K: Into<Vec<u8>>, V: Into<Vec<u8>>>(mut self, key: K, value: V) -> Self {
+				let key = key.into();
+				assert!(!key.is_empty());
+				self.genesis_init_mut().extra_storage.insert(key, value.into());
+				self
+			}
+
+			/// Build the test client.
+			fn build(self) -> Client<B> {
+				self.build_with_longest_chain().0
+			}
+
+			/// Build the test client and longest chain selector.
+			fn build_with_longest_chain(self) -> (Client<B>, $crate::client::LongestChain<B, $runtime::Block>);
+
+			/// Build the test client and the backend.
+			fn build_with_backend(self) -> (Client<B>, std::sync::Arc<B>);
+		}
+
+		impl<B> TestClientBuilderExt<B> for TestClientBuilder<
+			$crate::client::LocalCallExecutor<B, $crate::executor::NativeExecutor<LocalExecutor>>,
+			B
+		> where
+			B: $crate::client_api::backend::Backend<$runtime::Block>,
+			// Rust bug: https://github.com/rust-lang/rust/issues/24159
+			<B as $crate::client_api::backend::Backend<$runtime::Block>>::State:
+				$crate::state_machine::Backend<$crate::sp_runtime::traits::HasherFor<$runtime::Block>>,
+		{
+			fn genesis_init(&self) -> &GenesisParameters {
+				Self::genesis_init(self)
+			}
+
+			fn genesis_init_mut(&mut self) -> &mut GenesisParameters {
+				Self::genesis_init_mut(self)
+			}
+
+			fn build_with_longest_chain(self) -> (Client<B>, $crate::client::LongestChain<B, $runtime::Block>) {
+				self.build_with_native_executor(None)
+			}
+
+			fn build_with_backend(self) -> (Client<B>, std::sync::Arc<B>) {
+				let backend = self.backend();
+				(self.build_with_native_executor(None).0, backend)
+			}
+		}
+
+		/// Turnkey `execute_and_prove` / `check_execution_proof` helpers for the test client,
+		/// built directly on top of [`$crate::client_api::CallExecutor::prove_at_state`].
+		pub trait TestClientProofExt<B>
+		where
+			B: $crate::client_api::backend::Backend<$runtime::Block>,
+			<B as $crate::client_api::backend::Backend<$runtime::Block>>::State:
+				$crate::state_machine::Backend<$crate::sp_runtime::traits::HasherFor<$runtime::Block>>,
+		{
+			/// Executes `method` at block `at`, returning both its result and a `StorageProof`
+			/// that a light client could use to verify that result against `at`'s state root.
+			fn execute_and_prove(
+				&self,
+				at: &$crate::sp_runtime::generic::BlockId<$runtime::Block>,
+				method: &str,
+				call_data: &[u8],
+			) -> $crate::sp_blockchain::Result<(Vec<u8>, $crate::state_machine::StorageProof)>;
+
+			/// Verifies `proof` against `header`'s state root by reconstructing the trie it
+			/// describes and re-running `method` against it, returning the checked result or a
+			/// `$crate::sp_blockchain::Error` if the proof doesn't support the call.
+			fn check_execution_proof(
+				&self,
+				header: &<$runtime::Block as $crate::sp_runtime::traits::Block>::Header,
+				method: &str,
+				call_data: &[u8],
+				proof: $crate::state_machine::StorageProof,
+			) -> $crate::sp_blockchain::Result<Vec<u8>>;
+		}
+
+		impl<B> TestClientProofExt<B> for Client<B>
+		where
+			B: $crate::client_api::backend::Backend<$runtime::Block>,
+			<B as $crate::client_api::backend::Backend<$runtime::Block>>::State:
+				$crate::state_machine::Backend<$crate::sp_runtime::traits::HasherFor<$runtime::Block>>,
+		{
+			fn execute_and_prove(
+				&self,
+				at: &$crate::sp_runtime::generic::BlockId<$runtime::Block>,
+				method: &str,
+				call_data: &[u8],
+			) -> $crate::sp_blockchain::Result<(Vec<u8>, $crate::state_machine::StorageProof)> {
+				use $crate::client_api::CallExecutor;
+
+				let state = self.state_at(at)?;
+				let mut overlay = $crate::state_machine::OverlayedChanges::default();
+				self.executor().prove_at_state(state, &mut overlay, method, call_data)
+			}
+
+			fn check_execution_proof(
+				&self,
+				header: &<$runtime::Block as $crate::sp_runtime::traits::Block>::Header,
+				method: &str,
+				call_data: &[u8],
+				proof: $crate::state_machine::StorageProof,
+			) -> $crate::sp_blockchain::Result<Vec<u8>> {
+				use $crate::sp_runtime::traits::Header as HeaderT;
+				use $crate::client_api::CallExecutor;
+
+				let trie_backend = $crate::state_machine::create_proof_check_backend::<
+					$crate::sp_runtime::traits::HasherFor<$runtime::Block>
+				>(*header.state_root(), proof)
+					.map_err(|e| $crate::sp_blockchain::Error::Execution(Box::new(e.to_string())))?;
+				let mut overlay = $crate::state_machine::OverlayedChanges::default();
+				self.executor().prove_at_trie_state(&trie_backend, &mut overlay, method, call_data)
+					.map(|(result, _proof)| result)
+			}
+		}
+
+		/// Type of optional fetch callback.
+		type MaybeFetcherCallback<Req, Resp> = Option<Box<dyn Fn(Req) -> Result<Resp, $crate::sp_blockchain::Error> + Send + Sync>>;
+
+		/// Type of fetcher future result.
+		type FetcherFutureResult<Resp> = $crate::futures::future::Ready<Result<Resp, $crate::sp_blockchain::Error>>;
+
+		/// Implementation of light client fetcher used in tests.
+		///
+		/// When no callback is set for `remote_read` or `remote_read_child`, the fetcher does
+		/// not simply stub out the result: it builds the genesis trie for `genesis`, uses it
+		/// to generate a `StorageProof` for the request the same way a full node would, and
+		/// then verifies that proof against the state root carried by the request's header
+		/// before handing back the verified value. This exercises the same proof-checking
+		/// code paths that the production light call executor relies on.
+		///
+		/// The other request kinds build no proof without a callback: `remote_header`
+		/// answers only block 0, with the header computed from `genesis`; `remote_changes`
+		/// answers only a range covering just the genesis block, with an empty result, and
+		/// errors otherwise; `remote_call` and `remote_body` panic.
+		///
+		/// `genesis` defaults to `GenesisParameters::default()`, which only matches a
+		/// `TestClient` built without any `TestClientBuilderExt` customization; use
+		/// `with_genesis` to verify against a client whose genesis was customized (e.g. via
+		/// `set_support_changes_trie`, `set_authorities` or `add_extra_storage`).
+		#[derive(Default)]
+		pub struct LightFetcher {
+			genesis: GenesisParameters,
+			header: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteHeaderRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				<$runtime::Block as $crate::sp_runtime::traits::Block>::Header,
+			>,
+			read: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteReadRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				std::collections::HashMap<Vec<u8>, Option<Vec<u8>>>,
+			>,
+			read_child: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteReadChildRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				std::collections::HashMap<Vec<u8>, Option<Vec<u8>>>,
+			>,
+			call: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteCallRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				Vec<u8>,
+			>,
+			changes: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteChangesRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				Vec<($crate::sp_runtime::traits::NumberFor<$runtime::Block>, u32)>,
+			>,
+			body: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteBodyRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				Vec<<$runtime::Block as $crate::sp_runtime::traits::Block>::Extrinsic>,
+			>,
+		}
+
+		impl LightFetcher {
+			/// Sets the genesis parameters the no-callback code paths verify proofs against.
+			///
+			/// Pass the same `GenesisParameters` used to build the `TestClient` under test
+			/// (available via `TestClientBuilderExt::genesis_init`) so that headers and proofs
+			/// are checked against that client's actual genesis state root rather than the
+			/// default one. Note that `remote_read_child`'s no-callback path still can't honestly
+			/// prove individual child-trie reads, so it errors out if `genesis` has any
+			/// `add_extra_child_storage` entries; set a `with_remote_read_child` callback for
+			/// that case instead.
+			pub fn with_genesis(self, genesis: GenesisParameters) -> Self {
+				LightFetcher { genesis, ..self }
+			}
+
+			/// Sets remote header callback.
+			pub fn with_remote_header(self, header: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteHeaderRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				<$runtime::Block as $crate::sp_runtime::traits::Block>::Header,
+			>) -> Self {
+				LightFetcher { header, ..self }
+			}
+
+			/// Sets remote read callback.
+			pub fn with_remote_read(self, read: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteReadRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				std::collections::HashMap<Vec<u8>, Option<Vec<u8>>>,
+			>) -> Self {
+				LightFetcher { read, ..self }
+			}
+
+			/// Sets remote read child callback.
+			pub fn with_remote_read_child(self, read_child: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteReadChildRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				std::collections::HashMap<Vec<u8>, Option<Vec<u8>>>,
+			>) -> Self {
+				LightFetcher { read_child, ..self }
+			}
+
+			/// Sets remote call callback.
+			pub fn with_remote_call(self, call: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteCallRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				Vec<u8>,
+			>) -> Self {
+				LightFetcher { call, ..self }
+			}
+
+			/// Sets remote changes callback.
+			pub fn with_remote_changes(self, changes: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteChangesRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				Vec<($crate::sp_runtime::traits::NumberFor<$runtime::Block>, u32)>,
+			>) -> Self {
+				LightFetcher { changes, ..self }
+			}
+
+			/// Sets remote body callback.
+			pub fn with_remote_body(self, body: MaybeFetcherCallback<
+				$crate::client::light::fetcher::RemoteBodyRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+				Vec<<$runtime::Block as $crate::sp_runtime::traits::Block>::Extrinsic>,
+			>) -> Self {
+				LightFetcher { body, ..self }
+			}
+
+			/// Builds the backend holding `self.genesis`'s state, used to stand in for the
+			/// "remote" full node when verifying proofs in the default (no-callback) code
+			/// paths.
+			///
+			/// Its top-level trie includes the same child-root pointer entries
+			/// `GenesisInit::genesis_storage` chains in when computing the real genesis state
+			/// root, so top-level proofs (`remote_read`, and `remote_header`'s own state root)
+			/// match the actual client; it does not hold the child tries themselves, so it
+			/// cannot answer `remote_read_child`.
+			fn genesis_backend(&self) -> $crate::state_machine::InMemoryBackend<$crate::sp_runtime::traits::HasherFor<$runtime::Block>> {
+				use $crate::codec::Encode;
+				use $crate::generic_test_client::GenesisInit;
+				use $crate::sp_runtime::traits::{Block as BlockT, Header as HeaderT, Hash as HashT};
+
+				let storage = self.genesis.genesis_storage();
+				let child_roots = storage.1.iter().map(|(sk, child_map)| {
+					let state_root = <<<$runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+						child_map.clone().into_iter().collect()
+					);
+					(sk.clone(), state_root.encode())
+				});
+				storage.0.into_iter().chain(child_roots).collect::<Vec<_>>().into()
+			}
+		}
+
+		impl $crate::client::light::fetcher::Fetcher<$runtime::Block> for LightFetcher {
+			type RemoteHeaderResult = FetcherFutureResult<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>;
+			type RemoteReadResult = FetcherFutureResult<std::collections::HashMap<Vec<u8>, Option<Vec<u8>>>>;
+			type RemoteCallResult = FetcherFutureResult<Vec<u8>>;
+			type RemoteChangesResult = FetcherFutureResult<Vec<($crate::sp_runtime::traits::NumberFor<$runtime::Block>, u32)>>;
+			type RemoteBodyResult = FetcherFutureResult<Vec<<$runtime::Block as $crate::sp_runtime::traits::Block>::Extrinsic>>;
+
+			fn remote_header(
+				&self,
+				req: $crate::client::light::fetcher::RemoteHeaderRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+			) -> Self::RemoteHeaderResult {
+				use $crate::codec::Encode;
+				use $crate::sp_runtime::traits::{Block as BlockT, Header as HeaderT, Hash as HashT};
+				use $crate::generic_test_client::GenesisInit;
+
+				$crate::futures::future::ready(match self.header {
+					Some(ref header) => header(req),
+					None => {
+						// Only the genesis header is known to the in-memory "remote" backend
+						// used for verification, so that's the only header we can prove here.
+						if req.block != $crate::sp_runtime::traits::NumberFor::<$runtime::Block>::from(0u32) {
+							return $crate::futures::future::ready(
+								Err($crate::sp_blockchain::Error::UnknownBlock(format!("{}", req.block)))
+							);
+						}
+
+						// Mirror `GenesisInit::genesis_storage`'s own state root computation,
+						// chaining in child-root pointer entries, so this matches the real
+						// genesis block's state root for genesis with child storage too.
+						let genesis_storage = self.genesis.genesis_storage();
+						let child_roots = genesis_storage.1.iter().map(|(sk, child_map)| {
+							let state_root = <<<$runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+								child_map.clone().into_iter().collect()
+							);
+							(sk.clone(), state_root.encode())
+						});
+						let state_root = <<<$runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+							genesis_storage.0.clone().into_iter().chain(child_roots).collect()
+						);
+						let block: $runtime::Block = $crate::client::genesis::construct_genesis_block(state_root);
+						Ok(block.deconstruct().0)
+					}
+				})
+			}
+
+			fn remote_read(
+				&self,
+				req: $crate::client::light::fetcher::RemoteReadRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+			) -> Self::RemoteReadResult {
+				use $crate::sp_runtime::traits::Header as HeaderT;
+
+				if let Some(ref read) = self.read {
+					return $crate::futures::future::ready(read(req));
+				}
+
+				let backend = self.genesis_backend();
+				let proof = match $crate::state_machine::prove_read(backend, req.keys.iter().map(|key| key.as_slice())) {
+					Ok(proof) => proof,
+					Err(e) => return $crate::futures::future::ready(
+						Err($crate::sp_blockchain::Error::Execution(Box::new(e.to_string())))
+					),
+				};
+				$crate::futures::future::ready(
+					$crate::state_machine::read_proof_check::<$crate::sp_runtime::traits::HasherFor<$runtime::Block>, _>(
+						*req.header.state_root(),
+						proof,
+						req.keys.iter(),
+					).map_err(|e| $crate::sp_blockchain::Error::Execution(Box::new(e.to_string())))
+				)
+			}
+
+			fn remote_read_child(
+				&self,
+				req: $crate::client::light::fetcher::RemoteReadChildRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+			) -> Self::RemoteReadResult {
+				use $crate::sp_runtime::traits::Header as HeaderT;
+
+				if let Some(ref read_child) = self.read_child {
+					return $crate::futures::future::ready(read_child(req));
+				}
+
+				// `genesis_backend` only carries each child trie's root as a top-level pointer
+				// entry, not the child tries themselves, so it can't honestly answer a child
+				// read proof for a genesis that actually has child storage.
+				if !self.genesis.genesis_storage().1.is_empty() {
+					return $crate::futures::future::ready(Err($crate::sp_blockchain::Error::Backend(
+						"remote_read_child proof verification against genesis child storage \
+						 requires a real child trie backend, set a callback with \
+						 `with_remote_read_child` to test it".into(),
+					)));
+				}
+
+				let backend = self.genesis_backend();
+				let proof = match $crate::state_machine::prove_child_read(
+					backend,
+					&req.storage_key,
+					req.keys.iter().map(|key| key.as_slice()),
+				) {
+					Ok(proof) => proof,
+					Err(e) => return $crate::futures::future::ready(
+						Err($crate::sp_blockchain::Error::Execution(Box::new(e.to_string())))
+					),
+				};
+				$crate::futures::future::ready(
+					$crate::state_machine::read_child_proof_check::<$crate::sp_runtime::traits::HasherFor<$runtime::Block>, _>(
+						*req.header.state_root(),
+						proof,
+						&req.storage_key,
+						req.keys.iter(),
+					).map_err(|e| $crate::sp_blockchain::Error::Execution(Box::new(e.to_string())))
+				)
+			}
+
+			fn remote_call(
+				&self,
+				req: $crate::client::light::fetcher::RemoteCallRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+			) -> Self::RemoteCallResult {
+				match self.call {
+					Some(ref call) => $crate::futures::future::ready(call(req)),
+					None => unimplemented!(),
+				}
+			}
+
+			fn remote_changes(
+				&self,
+				req: $crate::client::light::fetcher::RemoteChangesRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+			) -> Self::RemoteChangesResult {
+				$crate::futures::future::ready(match self.changes {
+					Some(ref changes) => changes(req),
+					None => {
+						// The in-memory "remote" backend used to verify the other request kinds
+						// only ever holds genesis state, which has no changes trie of its own.
+						// The only changes proof it can honestly stand behind is "genesis has no
+						// changes", so verify the request isn't asking about a range that could
+						// contain any — i.e. that it starts at genesis and covers just that one
+						// block — and hand back an empty result; anything else needs a real
+						// callback.
+						if req.tries_roots.2.is_empty()
+							&& req.first_block.0 == $crate::sp_runtime::traits::NumberFor::<$runtime::Block>::from(0u32)
+							&& req.first_block.0 == req.last_block.0
+						{
+							Ok(Vec::new())
+						} else {
+							Err($crate::sp_blockchain::Error::Backend(
+								"remote_changes proof verification requires a real changes trie, \
+								 set a callback with `with_remote_changes` to test it".into(),
+							))
+						}
+					}
+				})
+			}
+
+			fn remote_body(
+				&self,
+				req: $crate::client::light::fetcher::RemoteBodyRequest<<$runtime::Block as $crate::sp_runtime::traits::Block>::Header>,
+			) -> Self::RemoteBodyResult {
+				match self.body {
+					Some(ref body) => $crate::futures::future::ready(body(req)),
+					None => unimplemented!(),
+				}
+			}
+		}
+
+		/// Creates new client instance used for tests.
+		pub fn new() -> Client<Backend> {
+			TestClientBuilder::new().build()
+		}
+
+		/// Creates new light client instance used for tests.
+		pub fn new_light() -> (
+			$crate::client::Client<LightBackend, LightExecutor, $runtime::Block, $runtime::RuntimeApi>,
+			std::sync::Arc<LightBackend>,
+		) {
+			let storage = $crate::client_db::light::LightStorage::new_test();
+			let blockchain = std::sync::Arc::new($crate::client::light::blockchain::Blockchain::new(storage));
+			let backend = std::sync::Arc::new(LightBackend::new(blockchain.clone()));
+			let executor = $crate::NativeExecutor::new($crate::WasmExecutionMethod::Interpreted, None);
+			let local_call_executor = $crate::client::LocalCallExecutor::new(backend.clone(), executor);
+			let call_executor = LightExecutor::new(
+				backend.clone(),
+				local_call_executor,
+			);
+
+			(
+				TestClientBuilder::with_backend(backend.clone())
+					.build_with_executor(call_executor)
+					.0,
+				backend,
+			)
+		}
+
+		/// Creates new light client fetcher used for tests.
+		///
+		/// Its no-callback paths verify against the default genesis; call `with_genesis` with
+		/// the `GenesisParameters` a particular `TestClient` was built from (via
+		/// `TestClientBuilderExt::genesis_init`) to verify against that client instead.
+		pub fn new_light_fetcher() -> LightFetcher {
+			LightFetcher::default()
+		}
+	};
+}